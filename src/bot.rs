@@ -1,64 +1,133 @@
+use std::collections::HashMap;
+
 use anyhow::{anyhow, Result};
 use async_openai::types::{
     ChatCompletionMessageToolCall, ChatCompletionRequestAssistantMessage,
-    ChatCompletionRequestAssistantMessageAudio, ChatCompletionRequestAssistantMessageContent,
-    ChatCompletionRequestMessage, ChatCompletionRequestToolMessage,
-    ChatCompletionRequestToolMessageContent, ChatCompletionResponseMessage,
-    ChatCompletionToolChoiceOption, CompletionUsage, CreateChatCompletionRequest,
-    CreateChatCompletionResponse,
+    ChatCompletionRequestAssistantMessageContent, ChatCompletionRequestMessage,
+    ChatCompletionRequestToolMessage, ChatCompletionRequestToolMessageContent,
+    ChatCompletionToolChoiceOption, CompletionUsage, CreateChatCompletionRequest, FunctionCall,
 };
+use futures::{future::join_all, StreamExt};
 use serde_json::Value;
 
 use crate::{config::Config, rpc::Callables, working_memory::WorkingMemory};
 
+// Accumulates the deltas for one in-progress tool call across stream chunks.
+#[derive(Default)]
+struct PartialToolCall {
+    id: Option<String>,
+    name: Option<String>,
+    arguments: String,
+}
+
 pub async fn bot_next(
     config: &Config,
     history: &mut WorkingMemory,
     callables: &Callables,
 ) -> Result<()> {
+    history.compact(config).await?;
+
     let request = CreateChatCompletionRequest {
         model: config.openai_model.clone(),
         messages: history.messages(),
-        tools: Some(callables.tools()),
+        tools: Some(callables.tools(config.strict_tools)),
         tool_choice: Some(ChatCompletionToolChoiceOption::Required),
         parallel_tool_calls: config.parallel_tool_calls,
         ..Default::default()
     };
 
-    let response: CreateChatCompletionResponse =
-        config.openai_client().chat().create(request).await?;
+    let mut stream = config.openai_client().chat().create_stream(request).await?;
 
-    if let Some(usage) = response.usage.as_ref() {
-        tracing::info!("{}", display_usage(usage));
-    }
+    let mut content = String::new();
+    let mut partial_tool_calls: HashMap<u32, PartialToolCall> = HashMap::new();
+
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
 
-    if response.choices.len() != 1 {
-        tracing::warn!("Expected 1 choice, got {}", response.choices.len());
+        if let Some(usage) = chunk.usage.as_ref() {
+            tracing::info!("{}", display_usage(usage));
+        }
+
+        let Some(choice) = chunk.choices.first() else {
+            continue;
+        };
+
+        if let Some(piece) = choice.delta.content.as_ref() {
+            eprint!("{piece}");
+            content.push_str(piece);
+        }
+
+        for delta in choice.delta.tool_calls.iter().flatten() {
+            let partial = partial_tool_calls.entry(delta.index).or_default();
+            if let Some(id) = delta.id.as_ref() {
+                partial.id.get_or_insert_with(|| id.clone());
+            }
+            if let Some(function) = delta.function.as_ref() {
+                if let Some(name) = function.name.as_ref() {
+                    partial.name.get_or_insert_with(|| name.clone());
+                }
+                if let Some(arguments) = function.arguments.as_ref() {
+                    partial.arguments.push_str(arguments);
+                }
+            }
+        }
+    }
+    if !content.is_empty() {
+        eprintln!();
     }
 
-    let response = response
-        .choices
-        .first()
-        .ok_or_else(|| anyhow!("No choices in response"))?;
+    let mut indices: Vec<u32> = partial_tool_calls.keys().copied().collect();
+    indices.sort_unstable();
 
-    let tool_calls: Vec<ChatCompletionMessageToolCall> = response
-        .clone()
-        .message
-        .tool_calls
-        .ok_or_else(|| anyhow!("No tool calls in response"))?;
+    let mut tool_calls = Vec::new();
+    let mut inputs = Vec::new();
+    for index in indices {
+        let partial = partial_tool_calls.remove(&index).unwrap();
+        let id = partial
+            .id
+            .ok_or_else(|| anyhow!("tool call is missing an id"))?;
+        let name = partial
+            .name
+            .ok_or_else(|| anyhow!("tool call is missing a name"))?;
+        let input: Value = serde_json::from_str(&partial.arguments)
+            .map_err(|_| anyhow!("tool call '{name}' arguments are not valid JSON"))?;
+        tool_calls.push(ChatCompletionMessageToolCall {
+            id,
+            r#type: Default::default(),
+            function: FunctionCall {
+                name,
+                arguments: partial.arguments,
+            },
+        });
+        inputs.push(input);
+    }
 
     if tool_calls.is_empty() {
         tracing::warn!("No tool calls in response");
     }
 
     let mut new_history: Vec<ChatCompletionRequestMessage> =
-        vec![ChatCompletionRequestMessage::Assistant(to_request_message(
-            response.clone().message,
-        ))];
+        vec![ChatCompletionRequestMessage::Assistant(
+            ChatCompletionRequestAssistantMessage {
+                content: (!content.is_empty())
+                    .then(|| ChatCompletionRequestAssistantMessageContent::Text(content)),
+                tool_calls: (!tool_calls.is_empty()).then(|| tool_calls.clone()),
+                ..Default::default()
+            },
+        )];
+
+    // Run every tool call concurrently; the model expects each tool_call_id to be
+    // answered, but doesn't care about wall-clock order, so we can join_all and keep
+    // the original ordering from the zipped Vecs.
+    let outputs = join_all(
+        tool_calls
+            .iter()
+            .zip(inputs)
+            .map(|(tool_call, input)| callables.call(&tool_call.function.name, input)),
+    )
+    .await;
 
-    for tool_call in tool_calls {
-        let input = serde_json::from_str(&tool_call.function.arguments)?;
-        let output = callables.call(&tool_call.function.name, input).await;
+    for (tool_call, output) in tool_calls.into_iter().zip(outputs) {
         let output = serde_json::to_string(&output)?;
         new_history.push(ChatCompletionRequestMessage::Tool(
             ChatCompletionRequestToolMessage {
@@ -73,29 +142,6 @@ pub async fn bot_next(
     Ok(())
 }
 
-fn to_request_message(
-    response_message: ChatCompletionResponseMessage,
-) -> ChatCompletionRequestAssistantMessage {
-    #[allow(deprecated)]
-    let ChatCompletionResponseMessage {
-        content,
-        refusal,
-        tool_calls,
-        role: _,
-        function_call,
-        audio,
-    } = response_message;
-    #[allow(deprecated)]
-    ChatCompletionRequestAssistantMessage {
-        content: content.map(ChatCompletionRequestAssistantMessageContent::Text),
-        name: None,
-        audio: audio.map(|audio| ChatCompletionRequestAssistantMessageAudio { id: audio.id }),
-        tool_calls,
-        function_call,
-        refusal,
-    }
-}
-
 fn display_usage(usage: &CompletionUsage) -> String {
     let mut value = serde_json::to_value(usage).unwrap();
     remove_zeros(&mut value);
@@ -1,6 +1,7 @@
 use std::path::PathBuf;
 
 use async_openai::{config::OpenAIConfig, Client};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value};
 use xdg::BaseDirectories;
@@ -11,12 +12,62 @@ pub struct Config {
     #[serde(default = "default_model")]
     pub openai_model: String,
     pub parallel_tool_calls: Option<bool>,
+    pub openai_base_url: Option<String>,
+    pub openai_org_id: Option<String>,
+    #[serde(default)]
+    pub extra_headers: std::collections::HashMap<String, String>,
+    // OpenAI-compatible backends (Azure, local servers, gateways) often don't support
+    // the "strict" structured-output mode OpenAI itself requires, so this defaults to on
+    // and lets those deployments opt out.
+    #[serde(default = "default_strict_tools")]
+    pub strict_tools: bool,
+    // Approximate (chars/4) token budget for the transcript sent to the model; once
+    // exceeded, WorkingMemory folds the oldest turns into a summary.
+    #[serde(default = "default_budget_tokens")]
+    pub budget_tokens: usize,
+    #[serde(default = "default_keep_recent_turns")]
+    pub keep_recent_turns: usize,
+    #[serde(default = "default_summary_model")]
+    pub summary_model: String,
+    // Default per-command timeout for the `run` callable; `null` means no timeout, and an
+    // absent field uses 120 seconds.
+    // Individual calls may override this via `RunArgs::timeout_secs`.
+    #[serde(default = "default_run_timeout_secs")]
+    pub run_timeout_secs: Option<u64>,
+    // Default max bytes kept per stdout/stderr stream for the `run` callable before the
+    // rest is replaced with a truncation marker.
+    #[serde(default = "default_run_max_output_bytes")]
+    pub run_max_output_bytes: usize,
 }
 
 fn default_model() -> String {
     "o1".to_string()
 }
 
+fn default_strict_tools() -> bool {
+    true
+}
+
+fn default_budget_tokens() -> usize {
+    80_000
+}
+
+fn default_keep_recent_turns() -> usize {
+    8
+}
+
+fn default_summary_model() -> String {
+    "gpt-4o-mini".to_string()
+}
+
+fn default_run_timeout_secs() -> Option<u64> {
+    Some(120)
+}
+
+fn default_run_max_output_bytes() -> usize {
+    200_000
+}
+
 fn config_path() -> anyhow::Result<PathBuf> {
     if let Ok(path) = std::env::var("BOT_CONFIG") {
         return Ok(PathBuf::from(path));
@@ -50,10 +101,78 @@ impl Config {
             ret["parallel_tool_calls"] = Value::Bool(!parallel_tool_calls.is_empty());
         }
 
+        if let Ok(base_url) = std::env::var("BOT_BASE_URL").or_else(|_| std::env::var("OPENAI_API_BASE")) {
+            ret["openai_base_url"] = Value::String(base_url);
+        }
+
+        if let Ok(org_id) = std::env::var("OPENAI_ORG_ID") {
+            ret["openai_org_id"] = Value::String(org_id);
+        }
+
+        if let Ok(strict_tools) = std::env::var("BOT_STRICT_TOOLS") {
+            ret["strict_tools"] = Value::Bool(!strict_tools.is_empty());
+        }
+
+        if let Ok(budget_tokens) = std::env::var("BOT_BUDGET_TOKENS") {
+            ret["budget_tokens"] = Value::Number(budget_tokens.parse()?);
+        }
+
+        if let Ok(keep_recent_turns) = std::env::var("BOT_KEEP_RECENT_TURNS") {
+            ret["keep_recent_turns"] = Value::Number(keep_recent_turns.parse()?);
+        }
+
+        if let Ok(summary_model) = std::env::var("BOT_SUMMARY_MODEL") {
+            ret["summary_model"] = Value::String(summary_model);
+        }
+
+        if let Ok(run_timeout_secs) = std::env::var("BOT_RUN_TIMEOUT_SECS") {
+            ret["run_timeout_secs"] = if run_timeout_secs.is_empty() {
+                Value::Null
+            } else {
+                Value::Number(run_timeout_secs.parse()?)
+            };
+        }
+
+        if let Ok(run_max_output_bytes) = std::env::var("BOT_RUN_MAX_OUTPUT_BYTES") {
+            ret["run_max_output_bytes"] = Value::Number(run_max_output_bytes.parse()?);
+        }
+
         serde_json::from_value(Value::Object(ret)).map_err(Into::into)
     }
 
     pub fn openai_client(&self) -> Client<OpenAIConfig> {
-        Client::with_config(OpenAIConfig::default().with_api_key(self.openai_api_key.clone()))
+        let mut config = OpenAIConfig::default().with_api_key(self.openai_api_key.clone());
+        if let Some(base_url) = self.openai_base_url.as_ref() {
+            config = config.with_api_base(base_url.clone());
+        }
+        if let Some(org_id) = self.openai_org_id.as_ref() {
+            config = config.with_org_id(org_id.clone());
+        }
+
+        if self.extra_headers.is_empty() {
+            return Client::with_config(config);
+        }
+
+        let http_client = reqwest::Client::builder()
+            .default_headers(self.header_map())
+            .build()
+            .expect("building reqwest client with extra_headers");
+        Client::with_config(config).with_http_client(http_client)
+    }
+
+    fn header_map(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in self.extra_headers.iter() {
+            let Ok(name) = HeaderName::try_from(name.as_str()) else {
+                tracing::warn!("ignoring invalid extra_headers key: {name}");
+                continue;
+            };
+            let Ok(value) = HeaderValue::try_from(value.as_str()) else {
+                tracing::warn!("ignoring invalid extra_headers value for {name}");
+                continue;
+            };
+            headers.insert(name, value);
+        }
+        headers
     }
 }
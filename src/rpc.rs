@@ -1,4 +1,8 @@
-use std::{collections::HashMap, future::Future};
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::Mutex,
+};
 
 use anyhow::{anyhow, Result};
 use async_openai::types::{ChatCompletionTool, ChatCompletionToolType, FunctionObject};
@@ -46,6 +50,10 @@ struct WrappedCallable {
 #[derive(Default)]
 pub struct Callables {
     callables: HashMap<String, WrappedCallable>,
+    // Keyed by (name, canonicalized input JSON); only populated for calls whose input
+    // declares itself `side_effect_free`, so a repeated `ls` or `cargo test` doesn't
+    // burn wall-clock time and tokens re-running deterministic output.
+    cache: Mutex<HashMap<(String, String), Value>>,
 }
 
 impl Callables {
@@ -78,10 +86,23 @@ impl Callables {
     }
 
     pub async fn call(&self, name: &str, input: Value) -> Value {
-        let ret = self
-            .call_inner(name, input.clone())
-            .await
-            .map_err(|e| format!("{}", e));
+        let side_effect_free = input
+            .get("side_effect_free")
+            .and_then(Value::as_bool)
+            .unwrap_or(false);
+        let cache_key =
+            side_effect_free.then(|| (name.to_string(), canonicalize(&cache_key_input(&input))));
+
+        if let Some(cache_key) = cache_key.as_ref() {
+            if let Some(cached) = self.cache.lock().unwrap().get(cache_key).cloned() {
+                tracing::info!("{} {} -> (cached) {}", name, input, cached);
+                return cached;
+            }
+        }
+
+        let call_result = self.call_inner(name, input.clone()).await;
+        let succeeded = call_result.is_ok();
+        let ret = call_result.map_err(|e| format!("{}", e));
         let ret = serde_json::to_value(&ret).unwrap();
         tracing::info!(
             "{} {} -> {}",
@@ -89,10 +110,20 @@ impl Callables {
             serde_json::to_string(&input).unwrap(),
             serde_json::to_string(&ret).unwrap()
         );
+
+        // Only cache successful calls: a failure (e.g. "file not found") is frequently
+        // transient in an agent loop that's actively creating the file it's about to
+        // re-check, and caching it would mislead the agent about its own fixed work.
+        if succeeded {
+            if let Some(cache_key) = cache_key {
+                self.cache.lock().unwrap().insert(cache_key, ret.clone());
+            }
+        }
+
         ret
     }
 
-    pub fn tools(&self) -> Vec<ChatCompletionTool> {
+    pub fn tools(&self, strict_tools: bool) -> Vec<ChatCompletionTool> {
         self.callables
             .iter()
             .map(|(name, callable)| ChatCompletionTool {
@@ -101,13 +132,51 @@ impl Callables {
                     name: name.clone(),
                     description: Some(callable.description.clone()),
                     parameters: Some(serde_json::to_value(&callable.input_schema).unwrap()),
-                    strict: Some(true),
+                    strict: strict_tools.then_some(true),
                 },
             })
             .collect_vec()
     }
 }
 
+// Fields the model fills in with free-form prose on every call (e.g. "explanation") rather
+// than semantic arguments; two calls that only differ in these shouldn't miss the cache.
+const CACHE_KEY_EXCLUDED_FIELDS: &[&str] = &["explanation"];
+
+fn cache_key_input(input: &Value) -> Value {
+    match input {
+        Value::Object(obj) => Value::Object(
+            obj.iter()
+                .filter(|(k, _)| !CACHE_KEY_EXCLUDED_FIELDS.contains(&k.as_str()))
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect(),
+        ),
+        other => other.clone(),
+    }
+}
+
+// Recursively sorts object keys so two JSON values that differ only in field order hash
+// to the same cache key.
+fn canonicalize(value: &Value) -> String {
+    fn sorted(value: &Value) -> Value {
+        match value {
+            Value::Object(obj) => {
+                let mut entries: Vec<(&String, &Value)> = obj.iter().collect();
+                entries.sort_by_key(|(k, _)| k.clone());
+                Value::Object(
+                    entries
+                        .into_iter()
+                        .map(|(k, v)| (k.clone(), sorted(v)))
+                        .collect(),
+                )
+            }
+            Value::Array(arr) => Value::Array(arr.iter().map(sorted).collect()),
+            other => other.clone(),
+        }
+    }
+    serde_json::to_string(&sorted(value)).unwrap()
+}
+
 fn schema_for<T>() -> RootSchema
 where
     T: JsonSchema,
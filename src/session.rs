@@ -0,0 +1,100 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs,
+    hash::{Hash, Hasher},
+    io::Write,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use async_openai::types::ChatCompletionRequestMessage;
+use xdg::BaseDirectories;
+
+fn sessions_dir() -> Result<PathBuf> {
+    let base = BaseDirectories::with_prefix("bot")?;
+    let placeholder = base.place_data_file("sessions/.keep")?;
+    Ok(placeholder.parent().unwrap().to_path_buf())
+}
+
+fn session_path(id: &str) -> Result<PathBuf> {
+    Ok(sessions_dir()?.join(format!("{id}.jsonl")))
+}
+
+/// Derive a short, filesystem-safe session id for a new session started with `directive`.
+/// The directive only contributes a human-friendly prefix and fingerprint for `--resume`
+/// to be recognizable by eye; the id also mixes in the current time so that rerunning the
+/// same directive (e.g. because the previous process crashed and the user forgot
+/// `--resume <id>`) starts a fresh session file instead of clobbering the old transcript.
+pub fn session_id_for_directive(directive: &str) -> String {
+    let slug: String = directive
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    let slug = slug
+        .split('-')
+        .filter(|s| !s.is_empty())
+        .take(6)
+        .collect::<Vec<_>>()
+        .join("-");
+
+    let mut hasher = DefaultHasher::new();
+    directive.hash(&mut hasher);
+    let hash = hasher.finish();
+
+    let started_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+
+    if slug.is_empty() {
+        format!("{hash:016x}-{started_at:x}")
+    } else {
+        format!("{slug}-{hash:016x}-{started_at:x}")
+    }
+}
+
+/// Overwrite the on-disk transcript for `id` with the full message history, so a crash or
+/// rate-limit mid-run never loses more than the in-flight `bot_next` call.
+pub fn save_session(id: &str, messages: &[ChatCompletionRequestMessage]) -> Result<()> {
+    let path = session_path(id)?;
+    let mut file = fs::File::create(path)?;
+    for message in messages {
+        writeln!(file, "{}", serde_json::to_string(message)?)?;
+    }
+    Ok(())
+}
+
+pub fn load_session(id: &str) -> Result<Vec<ChatCompletionRequestMessage>> {
+    let path = session_path(id)?;
+    let contents = fs::read_to_string(path)?;
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| serde_json::from_str(line).map_err(Into::into))
+        .collect()
+}
+
+pub fn list_sessions() -> Result<Vec<String>> {
+    let dir = sessions_dir()?;
+    let mut ids = Vec::new();
+    if dir.exists() {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) == Some("jsonl") {
+                if let Some(id) = path.file_stem().and_then(|s| s.to_str()) {
+                    ids.push(id.to_string());
+                }
+            }
+        }
+    }
+    ids.sort();
+    Ok(ids)
+}
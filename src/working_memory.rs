@@ -1,4 +1,12 @@
-use async_openai::types::ChatCompletionRequestMessage;
+use anyhow::Result;
+use async_openai::types::{
+    ChatCompletionRequestMessage, ChatCompletionRequestSystemMessage,
+    ChatCompletionRequestSystemMessageContent, ChatCompletionRequestUserMessageContent,
+    CreateChatCompletionRequest,
+};
+
+use crate::common::system;
+use crate::config::Config;
 
 #[derive(Default, Debug)]
 pub struct WorkingMemory {
@@ -15,4 +23,107 @@ impl WorkingMemory {
         tracing::trace!("{}", serde_json::to_string(&new_history).unwrap());
         self.fresh.extend(new_history);
     }
+
+    /// Fold the oldest tool-result turns into a single summarized system message once the
+    /// transcript grows past `config.budget_tokens`, so a long autonomous run never overflows
+    /// the model's context window. The original system prompt, the user directive, and the
+    /// most recent `config.keep_recent_turns` turns are always kept verbatim.
+    pub async fn compact(&mut self, config: &Config) -> Result<()> {
+        if approx_tokens(&self.fresh) <= config.budget_tokens {
+            return Ok(());
+        }
+
+        // messages[0] is the system prompt, messages[1] is the user directive; everything
+        // after is a sequence of turns, each starting with an Assistant message.
+        let head_len = 2.min(self.fresh.len());
+        let turn_starts: Vec<usize> = self.fresh[head_len..]
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| matches!(m, ChatCompletionRequestMessage::Assistant(_)))
+            .map(|(i, _)| head_len + i)
+            .collect();
+
+        if turn_starts.len() <= config.keep_recent_turns {
+            // Nothing old enough to fold.
+            return Ok(());
+        }
+
+        let keep_from = if config.keep_recent_turns == 0 {
+            // Nothing kept verbatim; fold every turn after the system prompt/directive.
+            self.fresh.len()
+        } else {
+            turn_starts[turn_starts.len() - config.keep_recent_turns]
+        };
+        if keep_from <= head_len {
+            return Ok(());
+        }
+
+        let to_fold = &self.fresh[head_len..keep_from];
+        let summary = summarize(config, to_fold).await?;
+
+        let mut compacted = Vec::with_capacity(head_len + 1 + (self.fresh.len() - keep_from));
+        compacted.extend_from_slice(&self.fresh[..head_len]);
+        compacted.push(system(format!(
+            "Summary of earlier tool interactions (preserving key facts, file paths, and unresolved problems):\n{summary}"
+        )));
+        compacted.extend_from_slice(&self.fresh[keep_from..]);
+
+        tracing::info!(
+            "compacted {} messages into a summary, {} remain",
+            to_fold.len(),
+            compacted.len()
+        );
+        self.fresh = compacted;
+
+        Ok(())
+    }
+}
+
+async fn summarize(config: &Config, messages: &[ChatCompletionRequestMessage]) -> Result<String> {
+    let transcript = serde_json::to_string_pretty(messages)?;
+    let request = CreateChatCompletionRequest {
+        model: config.summary_model.clone(),
+        messages: vec![
+            system(
+                "Summarize these tool interactions preserving key facts, file paths, and \
+                 unresolved problems. Be concise but don't drop anything load-bearing."
+                    .into(),
+            ),
+            ChatCompletionRequestMessage::System(ChatCompletionRequestSystemMessage {
+                content: ChatCompletionRequestSystemMessageContent::Text(transcript),
+                ..Default::default()
+            }),
+        ],
+        ..Default::default()
+    };
+
+    let response = config.openai_client().chat().create(request).await?;
+    let choice = response
+        .choices
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("no choices in summarization response"))?;
+    Ok(choice.message.content.unwrap_or_default())
+}
+
+fn approx_tokens(messages: &[ChatCompletionRequestMessage]) -> usize {
+    messages.iter().map(message_chars).sum::<usize>() / 4
+}
+
+fn message_chars(message: &ChatCompletionRequestMessage) -> usize {
+    match message {
+        ChatCompletionRequestMessage::System(m) => match &m.content {
+            ChatCompletionRequestSystemMessageContent::Text(s) => s.len(),
+            ChatCompletionRequestSystemMessageContent::Array(parts) => {
+                serde_json::to_string(parts).map(|s| s.len()).unwrap_or(0)
+            }
+        },
+        ChatCompletionRequestMessage::User(m) => match &m.content {
+            ChatCompletionRequestUserMessageContent::Text(s) => s.len(),
+            ChatCompletionRequestUserMessageContent::Array(parts) => {
+                serde_json::to_string(parts).map(|s| s.len()).unwrap_or(0)
+            }
+        },
+        other => serde_json::to_string(other).map(|s| s.len()).unwrap_or(0),
+    }
 }